@@ -0,0 +1,43 @@
+// Dispatcher provides the infrastructure to support the development of DApps,
+// mediating the communication between on-chain and off-chain components.
+
+// Copyright (C) 2019 Cartesi Pte. Ltd.
+
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later
+// version.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A
+// PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Compile every Cap'n Proto schema under `schemas/` so the optional
+//! `CapnpCodec` backend has generated readers/builders to work with. This is
+//! a no-op unless the `capnp` feature is enabled.
+
+use std::fs;
+use std::path::Path;
+
+fn main() {
+    if std::env::var_os("CARGO_FEATURE_CAPNP").is_none() {
+        return;
+    }
+
+    let schema_dir = Path::new("schemas");
+    println!("cargo:rerun-if-changed={}", schema_dir.display());
+
+    let mut command = capnpc::CompilerCommand::new();
+    command.src_prefix(schema_dir);
+    for entry in fs::read_dir(schema_dir).expect("schemas directory not found") {
+        let path = entry.expect("unreadable schema entry").path();
+        if path.extension().and_then(|e| e.to_str()) == Some("capnp") {
+            println!("cargo:rerun-if-changed={}", path.display());
+            command.file(&path);
+        }
+    }
+    command.run().expect("failed to compile Cap'n Proto schemas");
+}
@@ -27,10 +27,72 @@
 //! A collection of types that represent the manager grpc interface
 //! together with the conversion functions from the automatically
 //! generated types.
+//!
+//! TODO(chunk0-4): an event-loop-friendly `EmulatorClient` over the six
+//! `EMULATOR_METHOD_*` endpoints is still outstanding. The earlier attempt
+//! layered `std`-async on top of the futures-0.1 `grpc` stack this module is
+//! built on, with no concrete transport and no call site, so it was removed
+//! rather than shipped. A real client belongs on `grpc::Client` (futures
+//! 0.1), reusing the marshalling below; this is left open until it can be
+//! implemented and integrated against the dispatcher.
 
 use super::ethereum_types::H256;
 use super::{cartesi_base, manager_high};
 use super::grpc::marshall::Marshaller;
+use sha3::{Digest, Keccak256};
+use std::convert::TryFrom;
+
+/// Errors raised while converting an automatically generated protobuf
+/// message into one of the typed wrapper structs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ManagerError {
+    /// An optional protobuf field the wrapper depends on was absent.
+    MissingField(&'static str),
+    /// A length-delimited value did not have the expected byte size.
+    WrongSize {
+        field: &'static str,
+        expected: usize,
+        found: usize,
+    },
+    /// The underlying marshaller failed to decode the wire bytes.
+    BadMarshalling,
+}
+
+/// Height of the Merkle tree covering the whole machine address space.
+/// Addresses are 64 bits wide, so a proof of a `log2_size` subtree carries
+/// `MACHINE_TREE_LOG2_SIZE - log2_size` sibling hashes.
+const MACHINE_TREE_LOG2_SIZE: u32 = 64;
+
+/// Smallest subtree a proof may address: an 8-byte word.
+const WORD_LOG2_SIZE: u32 = 3;
+
+/// Keccak256 of the concatenation of two tree nodes.
+fn hash_children(left: &H256, right: &H256) -> H256 {
+    let mut hasher = Keccak256::new();
+    hasher.input(left.as_bytes());
+    hasher.input(right.as_bytes());
+    H256::from_slice(&hasher.result())
+}
+
+/// Build an `H256` from a protobuf `content` slice, checking its length so
+/// a truncated message surfaces as an error instead of a panic.
+fn h256_from(field: &'static str, content: &[u8]) -> Result<H256, ManagerError> {
+    if content.len() != 32 {
+        return Err(ManagerError::WrongSize {
+            field,
+            expected: 32,
+            found: content.len(),
+        });
+    }
+    Ok(H256::from_slice(content))
+}
+
+/// Keccak256 of a single 8-byte word, i.e. the leaf hash of that word.
+fn hash_word(word: &[u8; 8]) -> H256 {
+    let mut hasher = Keccak256::new();
+    hasher.input(&word[..]);
+    H256::from_slice(&hasher.result())
+}
 
 pub const EMULATOR_SERVICE_NAME: &'static str = "emulator";
 pub const EMULATOR_METHOD_NEW: &'static str = "/CartesiManagerHigh.MachineManagerHigh/NewSession";
@@ -47,19 +109,21 @@ pub struct NewSessionRequest {
     pub session_id: String,
 }
 
-impl From<manager_high::NewSessionRequest>
+impl TryFrom<manager_high::NewSessionRequest>
     for NewSessionRequest
 {
-    fn from(
+    type Error = ManagerError;
+
+    fn try_from(
         result: manager_high::NewSessionRequest,
-    ) -> Self {
-        NewSessionRequest {
-            machine: result.machine
-                    .into_option()
-                    .expect("machine not found")
-                    .into(),
+    ) -> Result<Self, Self::Error> {
+        Ok(NewSessionRequest {
+            machine: result
+                .machine
+                .into_option()
+                .ok_or(ManagerError::MissingField("machine"))?,
             session_id: result.session_id,
-        }
+        })
     }
 }
 
@@ -89,20 +153,22 @@ pub struct SessionRunResult {
     pub hashes: Vec<H256>,
 }
 
-impl From<manager_high::SessionRunResult>
+impl TryFrom<manager_high::SessionRunResult>
     for SessionRunResult
 {
-    fn from(
+    type Error = ManagerError;
+
+    fn try_from(
         result: manager_high::SessionRunResult,
-    ) -> Self {
-        SessionRunResult {
+    ) -> Result<Self, Self::Error> {
+        Ok(SessionRunResult {
             hashes: result
                 .hashes
                 .into_vec()
                 .into_iter()
-                .map(|hash| H256::from_slice(&hash.content))
-                .collect(),
-        }
+                .map(|hash| h256_from("hash", &hash.content))
+                .collect::<Result<Vec<_>, _>>()?,
+        })
     }
 }
 
@@ -112,15 +178,17 @@ pub struct NewSessionResult {
     pub hash: H256,
 }
 
-impl From<cartesi_base::Hash>
+impl TryFrom<cartesi_base::Hash>
     for NewSessionResult
 {
-    fn from(
+    type Error = ManagerError;
+
+    fn try_from(
         result: cartesi_base::Hash,
-    ) -> Self {
-        NewSessionResult {
-            hash: H256::from_slice(&result.content)
-        }
+    ) -> Result<Self, Self::Error> {
+        Ok(NewSessionResult {
+            hash: h256_from("hash", &result.content)?,
+        })
     }
 }
 
@@ -151,32 +219,103 @@ pub struct Proof {
     pub root_hash: H256,
 }
 
-impl From<cartesi_base::Proof> for Proof {
-    fn from(proof: cartesi_base::Proof) -> Self {
-        Proof {
+impl TryFrom<cartesi_base::Proof> for Proof {
+    type Error = ManagerError;
+
+    fn try_from(proof: cartesi_base::Proof) -> Result<Self, Self::Error> {
+        Ok(Proof {
             address: proof.address,
             log2_size: proof.log2_size,
-            target_hash: H256::from_slice(
+            target_hash: h256_from(
+                "target_hash",
                 &proof
                     .target_hash
                     .into_option()
-                    .expect("target hash not found")
+                    .ok_or(ManagerError::MissingField("target_hash"))?
                     .content,
-            ),
+            )?,
             sibling_hashes: proof
                 .sibling_hashes
                 .into_vec()
                 .into_iter()
-                .map(|hash| H256::from_slice(&hash.content))
-                .collect(),
-            root_hash: H256::from_slice(
+                .map(|hash| h256_from("sibling_hash", &hash.content))
+                .collect::<Result<Vec<_>, _>>()?,
+            root_hash: h256_from(
+                "root_hash",
                 &proof
                     .root_hash
                     .into_option()
-                    .expect("root hash not found")
+                    .ok_or(ManagerError::MissingField("root_hash"))?
                     .content,
-            ),
+            )?,
+        })
+    }
+}
+
+/// Reasons a `Proof` can fail to verify against its own `root_hash`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProofError {
+    /// `log2_size` is smaller than a single 8-byte word.
+    TooSmall,
+    /// `log2_size` exceeds the height of the machine address-space tree.
+    TooLarge,
+    /// The number of sibling hashes does not match the tree height.
+    WrongSiblingCount { expected: usize, found: usize },
+    /// The hash recomputed from the leaf and the siblings does not match
+    /// the `root_hash` the proof claims.
+    RootMismatch { expected: H256, computed: H256 },
+}
+
+impl Proof {
+    /// Recompute the root hash from `target_hash` and the sibling chain and
+    /// check it against `root_hash`, the way a light-client verifies a trie
+    /// proof. Siblings are consumed from the least-significant level upward:
+    /// at level `i` the relevant bit of `address` is `log2_size + i`; a zero
+    /// bit means `node` is the left child, a one bit the right child.
+    pub fn verify(&self) -> Result<(), ProofError> {
+        if self.log2_size < WORD_LOG2_SIZE {
+            return Err(ProofError::TooSmall);
+        }
+        if self.log2_size > MACHINE_TREE_LOG2_SIZE {
+            return Err(ProofError::TooLarge);
+        }
+        let expected = (MACHINE_TREE_LOG2_SIZE - self.log2_size) as usize;
+        if self.sibling_hashes.len() != expected {
+            return Err(ProofError::WrongSiblingCount {
+                expected,
+                found: self.sibling_hashes.len(),
+            });
         }
+        let computed = self.recompute_root(self.target_hash);
+        if computed != self.root_hash {
+            return Err(ProofError::RootMismatch {
+                expected: self.root_hash,
+                computed,
+            });
+        }
+        Ok(())
+    }
+
+    /// Convenience wrapper over [`Proof::verify`] returning a boolean.
+    pub fn is_valid(&self) -> bool {
+        self.verify().is_ok()
+    }
+
+    /// Walk the sibling chain starting from an arbitrary `leaf` hash and
+    /// return the root it produces. With `leaf == target_hash` this is the
+    /// proof's own root; with the hash of a freshly written word it is the
+    /// root of the state that results from the write.
+    fn recompute_root(&self, leaf: H256) -> H256 {
+        let mut node = leaf;
+        for (i, sibling) in self.sibling_hashes.iter().enumerate() {
+            let height = self.log2_size + i as u32;
+            if (self.address >> height) & 1 == 0 {
+                node = hash_children(&node, sibling);
+            } else {
+                node = hash_children(sibling, &node);
+            }
+        }
+        node
     }
 }
 
@@ -190,42 +329,52 @@ pub struct Access {
     pub proof: Proof,
 }
 
-fn to_bytes(input: Vec<u8>) -> Option<[u8; 8]> {
+fn to_bytes(field: &'static str, input: Vec<u8>) -> Result<[u8; 8], ManagerError> {
     if input.len() != 8 {
-        None
+        Err(ManagerError::WrongSize {
+            field,
+            expected: 8,
+            found: input.len(),
+        })
     } else {
-        Some([
+        Ok([
             input[0], input[1], input[2], input[3], input[4], input[5],
             input[6], input[7],
         ])
     }
 }
 
-impl From<cartesi_base::Access> for Access {
-    fn from(access: cartesi_base::Access) -> Self {
-        let proof: Proof =
-            access.proof.into_option().expect("proof not found").into();
-        Access {
+impl TryFrom<cartesi_base::Access> for Access {
+    type Error = ManagerError;
+
+    fn try_from(access: cartesi_base::Access) -> Result<Self, Self::Error> {
+        let proof: Proof = Proof::try_from(
+            access
+                .proof
+                .into_option()
+                .ok_or(ManagerError::MissingField("proof"))?,
+        )?;
+        Ok(Access {
             operation: access.operation.into(),
             address: proof.address,
             value_read: to_bytes(
+                "value_read",
                 access
                     .read
                     .into_option()
-                    .expect("read access not found")
+                    .ok_or(ManagerError::MissingField("read"))?
                     .content,
-            )
-            .expect("read value has the wrong size"),
+            )?,
             value_written: to_bytes(
+                "value_written",
                 access
                     .written
                     .into_option()
-                    .expect("write access not found")
+                    .ok_or(ManagerError::MissingField("written"))?
                     .content,
-            )
-            .expect("write value has the wrong size"),
+            )?,
             proof: proof,
-        }
+        })
     }
 }
 
@@ -255,23 +404,88 @@ pub struct SessionStepResult {
     pub log: Vec<Access>,
 }
 
-impl From<manager_high::SessionStepResult>
+impl TryFrom<manager_high::SessionStepResult>
     for SessionStepResult
 {
-    fn from(
+    type Error = ManagerError;
+
+    fn try_from(
         result: manager_high::SessionStepResult,
-    ) -> Self {
-        SessionStepResult {
+    ) -> Result<Self, Self::Error> {
+        Ok(SessionStepResult {
             log: result
                 .log
                 .into_option()
-                .expect("log not found")
+                .ok_or(ManagerError::MissingField("log"))?
                 .accesses
                 .into_vec()
                 .into_iter()
-                .map(|hash| hash.into())
-                .collect(),
+                .map(Access::try_from)
+                .collect::<Result<Vec<_>, _>>()?,
+        })
+    }
+}
+
+/// Reasons a `SessionStepResult` access log fails to describe a legal step.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StepError {
+    /// The log is empty, so it cannot be bound to `pre`/`post`.
+    EmptyLog,
+    /// The `proof` of the access at `index` did not verify on its own.
+    BadProof { index: usize, source: ProofError },
+    /// The word read at `index` does not hash to the proof's leaf.
+    ReadMismatch { index: usize },
+    /// The running root before the access at `index` disagrees with the
+    /// root the access's proof is rooted at (broken chain).
+    RootMismatch { index: usize, expected: H256, found: H256 },
+    /// The post-state root after applying the whole log differs from the
+    /// `post` hash the caller supplied.
+    PostMismatch { expected: H256, found: H256 },
+}
+
+impl SessionStepResult {
+    /// Independently confirm that this access log describes a single legal
+    /// step taking the machine from `pre` to `post`. Every proof is checked
+    /// with [`Proof::verify`], reads are matched against their leaf, and the
+    /// per-access roots are required to chain from `pre` to `post`, applying
+    /// the written word at each `Write` access.
+    pub fn verify(&self, pre: H256, post: H256) -> Result<(), StepError> {
+        if self.log.is_empty() {
+            return Err(StepError::EmptyLog);
+        }
+        let mut expected = pre;
+        for (index, access) in self.log.iter().enumerate() {
+            access
+                .proof
+                .verify()
+                .map_err(|source| StepError::BadProof { index, source })?;
+            if access.proof.root_hash != expected {
+                return Err(StepError::RootMismatch {
+                    index,
+                    expected,
+                    found: access.proof.root_hash,
+                });
+            }
+            match access.operation {
+                AccessOperation::Read => {
+                    if hash_word(&access.value_read) != access.proof.target_hash {
+                        return Err(StepError::ReadMismatch { index });
+                    }
+                }
+                AccessOperation::Write => {
+                    expected = access
+                        .proof
+                        .recompute_root(hash_word(&access.value_written));
+                }
+            }
         }
+        if expected != post {
+            return Err(StepError::PostMismatch {
+                expected: post,
+                found: expected,
+            });
+        }
+        Ok(())
     }
 }
 
@@ -283,17 +497,22 @@ pub struct SessionReadMemoryRequest {
     pub position: cartesi_base::ReadMemoryRequest
 }
 
-impl From<manager_high::SessionReadMemoryRequest>
+impl TryFrom<manager_high::SessionReadMemoryRequest>
     for SessionReadMemoryRequest
 {
-    fn from(
+    type Error = ManagerError;
+
+    fn try_from(
         result: manager_high::SessionReadMemoryRequest,
-    ) -> Self {
-        SessionReadMemoryRequest {
+    ) -> Result<Self, Self::Error> {
+        Ok(SessionReadMemoryRequest {
             session_id: result.session_id,
             time: result.cycle,
-            position: result.position.into_option().expect("position not found").into()
-        }
+            position: result
+                .position
+                .into_option()
+                .ok_or(ManagerError::MissingField("position"))?,
+        })
     }
 }
 
@@ -317,15 +536,21 @@ pub struct SessionReadMemoryResult {
     pub read_content: ReadMemoryResponse
 }
 
-impl From<manager_high::SessionReadMemoryResult>
+impl TryFrom<manager_high::SessionReadMemoryResult>
     for SessionReadMemoryResult
 {
-    fn from(
+    type Error = ManagerError;
+
+    fn try_from(
         result: manager_high::SessionReadMemoryResult,
-    ) -> Self {
-        SessionReadMemoryResult {
-            read_content: result.read_content.into_option().expect("read_content not found").into()
-        }
+    ) -> Result<Self, Self::Error> {
+        Ok(SessionReadMemoryResult {
+            read_content: result
+                .read_content
+                .into_option()
+                .ok_or(ManagerError::MissingField("read_content"))?
+                .into(),
+        })
     }
 }
 
@@ -337,17 +562,22 @@ pub struct SessionGetProofRequest {
     pub target: cartesi_base::GetProofRequest
 }
 
-impl From<manager_high::SessionGetProofRequest>
+impl TryFrom<manager_high::SessionGetProofRequest>
     for SessionGetProofRequest
 {
-    fn from(
+    type Error = ManagerError;
+
+    fn try_from(
         result: manager_high::SessionGetProofRequest,
-    ) -> Self {
-        SessionGetProofRequest {
+    ) -> Result<Self, Self::Error> {
+        Ok(SessionGetProofRequest {
             session_id: result.session_id,
             time: result.cycle,
-            target: result.target.into_option().expect("target not found").into()
-        }
+            target: result
+                .target
+                .into_option()
+                .ok_or(ManagerError::MissingField("target"))?,
+        })
     }
 }
 
@@ -357,162 +587,715 @@ pub struct SessionGetProofResult {
     pub proof: Proof
 }
 
-impl From<cartesi_base::Proof>
+impl TryFrom<cartesi_base::Proof>
     for SessionGetProofResult
 {
-    fn from(
+    type Error = ManagerError;
+
+    fn try_from(
         proof: cartesi_base::Proof,
-    ) -> Self {
-        SessionGetProofResult {
-            proof: proof.into()
-        }
+    ) -> Result<Self, Self::Error> {
+        Ok(SessionGetProofResult {
+            proof: Proof::try_from(proof)?,
+        })
+    }
+}
+
+/// Wire format used to (de)serialize the session messages exchanged with the
+/// emulator manager. The protobuf implementation below is the default; an
+/// alternative backend (e.g. Cap'n Proto) can be plugged in without touching
+/// the typed wrapper structs, so sessions may negotiate a zero-copy framing
+/// for the large `SessionStepResult` access logs and `ReadMemoryResponse`
+/// blobs where protobuf parsing dominates latency.
+pub trait SessionCodec {
+    fn encode_new_session(&self, request: NewSessionRequest) -> Result<Vec<u8>, ManagerError>;
+    fn encode_run(&self, request: SessionRunRequest) -> Result<Vec<u8>, ManagerError>;
+    fn encode_step(&self, request: SessionStepRequest) -> Result<Vec<u8>, ManagerError>;
+    fn encode_read_memory(&self, request: SessionReadMemoryRequest) -> Result<Vec<u8>, ManagerError>;
+    fn encode_get_proof(&self, request: SessionGetProofRequest) -> Result<Vec<u8>, ManagerError>;
+
+    fn decode_new_session_request(&self, response: Vec<u8>) -> Result<NewSessionRequest, ManagerError>;
+    fn decode_new_session_result(&self, response: Vec<u8>) -> Result<NewSessionResult, ManagerError>;
+    fn decode_run_result(&self, response: Vec<u8>) -> Result<SessionRunResult, ManagerError>;
+    fn decode_step_result(&self, response: Vec<u8>) -> Result<SessionStepResult, ManagerError>;
+    fn decode_read_memory_result(&self, response: Vec<u8>) -> Result<SessionReadMemoryResult, ManagerError>;
+    fn decode_get_proof_result(&self, response: Vec<u8>) -> Result<SessionGetProofResult, ManagerError>;
+}
+
+/// The default [`SessionCodec`], framing every message with
+/// `grpc::protobuf::MarshallerProtobuf`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProtobufCodec;
+
+fn protobuf_read<M>(response: Vec<u8>) -> Result<M, ManagerError>
+where
+    M: protobuf::Message,
+{
+    let marshaller: Box<dyn Marshaller<M> + Sync + Send> =
+        Box::new(grpc::protobuf::MarshallerProtobuf);
+    marshaller
+        .read(bytes::Bytes::from(response))
+        .map_err(|_| ManagerError::BadMarshalling)
+}
+
+fn protobuf_write<M>(message: &M) -> Result<Vec<u8>, ManagerError>
+where
+    M: protobuf::Message,
+{
+    let marshaller: Box<dyn Marshaller<M> + Sync + Send> =
+        Box::new(grpc::protobuf::MarshallerProtobuf);
+    marshaller
+        .write(message)
+        .map_err(|_| ManagerError::BadMarshalling)
+}
+
+impl SessionCodec for ProtobufCodec {
+    fn encode_new_session(&self, request: NewSessionRequest) -> Result<Vec<u8>, ManagerError> {
+        let mut req = manager_high::NewSessionRequest::new();
+        req.set_session_id(request.session_id);
+        req.set_machine(request.machine);
+        protobuf_write(&req)
+    }
+
+    fn encode_run(&self, request: SessionRunRequest) -> Result<Vec<u8>, ManagerError> {
+        let mut req = manager_high::SessionRunRequest::new();
+        req.set_session_id(request.session_id);
+        req.set_final_cycles(request.times);
+        protobuf_write(&req)
+    }
+
+    fn encode_step(&self, request: SessionStepRequest) -> Result<Vec<u8>, ManagerError> {
+        let mut req = manager_high::SessionStepRequest::new();
+        req.set_session_id(request.session_id);
+        req.set_initial_cycle(request.time);
+        protobuf_write(&req)
+    }
+
+    fn encode_read_memory(&self, request: SessionReadMemoryRequest) -> Result<Vec<u8>, ManagerError> {
+        let mut req = manager_high::SessionReadMemoryRequest::new();
+        req.set_session_id(request.session_id);
+        req.set_cycle(request.time);
+        req.set_position(request.position);
+        protobuf_write(&req)
+    }
+
+    fn encode_get_proof(&self, request: SessionGetProofRequest) -> Result<Vec<u8>, ManagerError> {
+        let mut req = manager_high::SessionGetProofRequest::new();
+        req.set_session_id(request.session_id);
+        req.set_cycle(request.time);
+        req.set_target(request.target);
+        protobuf_write(&req)
+    }
+
+    fn decode_new_session_request(&self, response: Vec<u8>) -> Result<NewSessionRequest, ManagerError> {
+        NewSessionRequest::try_from(protobuf_read::<manager_high::NewSessionRequest>(response)?)
+    }
+
+    fn decode_new_session_result(&self, response: Vec<u8>) -> Result<NewSessionResult, ManagerError> {
+        NewSessionResult::try_from(protobuf_read::<cartesi_base::Hash>(response)?)
+    }
+
+    fn decode_run_result(&self, response: Vec<u8>) -> Result<SessionRunResult, ManagerError> {
+        SessionRunResult::try_from(protobuf_read::<manager_high::SessionRunResult>(response)?)
+    }
+
+    fn decode_step_result(&self, response: Vec<u8>) -> Result<SessionStepResult, ManagerError> {
+        SessionStepResult::try_from(protobuf_read::<manager_high::SessionStepResult>(response)?)
+    }
+
+    fn decode_read_memory_result(&self, response: Vec<u8>) -> Result<SessionReadMemoryResult, ManagerError> {
+        SessionReadMemoryResult::try_from(protobuf_read::<manager_high::SessionReadMemoryResult>(response)?)
+    }
+
+    fn decode_get_proof_result(&self, response: Vec<u8>) -> Result<SessionGetProofResult, ManagerError> {
+        SessionGetProofResult::try_from(protobuf_read::<cartesi_base::Proof>(response)?)
     }
 }
 
-impl From<Vec<u8>>
+// The `Vec<u8>` conversions delegate to the default protobuf codec. Decoding
+// is `TryFrom<Vec<u8>>` and returns a `ManagerError` on malformed input;
+// encoding is `From<...> for Vec<u8>` and panics if marshalling fails.
+
+impl TryFrom<Vec<u8>>
     for SessionRunResult
 {
-    fn from(
-        response: Vec<u8>,
-    ) -> Self {
-        let marshaller: Box<dyn Marshaller<manager_high::SessionRunResult> + Sync + Send> = Box::new(grpc::protobuf::MarshallerProtobuf);
-        marshaller.read(bytes::Bytes::from(response)).unwrap().into()
+    type Error = ManagerError;
+
+    fn try_from(response: Vec<u8>) -> Result<Self, Self::Error> {
+        ProtobufCodec.decode_run_result(response)
     }
 }
 
-impl From<Vec<u8>>
+impl TryFrom<Vec<u8>>
     for SessionStepResult
 {
-    fn from(
-        response: Vec<u8>,
-    ) -> Self {
-        let marshaller: Box<dyn Marshaller<manager_high::SessionStepResult> + Sync + Send> = Box::new(grpc::protobuf::MarshallerProtobuf);
-        marshaller.read(bytes::Bytes::from(response)).unwrap().into()
+    type Error = ManagerError;
+
+    fn try_from(response: Vec<u8>) -> Result<Self, Self::Error> {
+        ProtobufCodec.decode_step_result(response)
     }
 }
 
-impl From<Vec<u8>>
+impl TryFrom<Vec<u8>>
     for NewSessionResult
 {
-    fn from(
-        response: Vec<u8>,
-    ) -> Self {
-        let marshaller: Box<dyn Marshaller<cartesi_base::Hash> + Sync + Send> = Box::new(grpc::protobuf::MarshallerProtobuf);
-        marshaller.read(bytes::Bytes::from(response)).unwrap().into()
+    type Error = ManagerError;
+
+    fn try_from(response: Vec<u8>) -> Result<Self, Self::Error> {
+        ProtobufCodec.decode_new_session_result(response)
     }
 }
 
-impl From<Vec<u8>>
+impl TryFrom<Vec<u8>>
     for SessionReadMemoryResult
 {
-    fn from(
-        response: Vec<u8>,
-    ) -> Self {
-        let marshaller: Box<dyn Marshaller<manager_high::SessionReadMemoryResult> + Sync + Send> = Box::new(grpc::protobuf::MarshallerProtobuf);
-        marshaller.read(bytes::Bytes::from(response)).unwrap().into()
+    type Error = ManagerError;
+
+    fn try_from(response: Vec<u8>) -> Result<Self, Self::Error> {
+        ProtobufCodec.decode_read_memory_result(response)
     }
 }
 
-impl From<Vec<u8>>
+impl TryFrom<Vec<u8>>
     for SessionGetProofResult
 {
-    fn from(
-        response: Vec<u8>,
-    ) -> Self {
-        let marshaller: Box<dyn Marshaller<cartesi_base::Proof> + Sync + Send> = Box::new(grpc::protobuf::MarshallerProtobuf);
-        marshaller.read(bytes::Bytes::from(response)).unwrap().into()
+    type Error = ManagerError;
+
+    fn try_from(response: Vec<u8>) -> Result<Self, Self::Error> {
+        ProtobufCodec.decode_get_proof_result(response)
     }
 }
 
-impl From<Vec<u8>>
+impl TryFrom<Vec<u8>>
     for NewSessionRequest
 {
-    fn from(
-        response: Vec<u8>,
-    ) -> Self {
-        let marshaller: Box<dyn Marshaller<manager_high::NewSessionRequest> + Sync + Send> = Box::new(grpc::protobuf::MarshallerProtobuf);
-        marshaller.read(bytes::Bytes::from(response)).unwrap().into()
+    type Error = ManagerError;
+
+    fn try_from(response: Vec<u8>) -> Result<Self, Self::Error> {
+        ProtobufCodec.decode_new_session_request(response)
     }
 }
 
 impl From<SessionRunRequest>
     for Vec<u8>
 {
-    fn from(
-        request: SessionRunRequest,
-    ) -> Self {
-        let marshaller: Box<dyn Marshaller<manager_high::SessionRunRequest> + Sync + Send> = Box::new(grpc::protobuf::MarshallerProtobuf);
-    
-        let mut req = manager_high::SessionRunRequest::new();
-        req.set_session_id(request.session_id);
-        req.set_final_cycles(request.times);
-
-        marshaller.write(&req).unwrap()
+    fn from(request: SessionRunRequest) -> Self {
+        ProtobufCodec.encode_run(request).unwrap()
     }
 }
 
 impl From<SessionStepRequest>
     for Vec<u8>
 {
-    fn from(
-        request: SessionStepRequest,
-    ) -> Self {
-        let marshaller: Box<dyn Marshaller<manager_high::SessionStepRequest> + Sync + Send> = Box::new(grpc::protobuf::MarshallerProtobuf);
-    
-        let mut req = manager_high::SessionStepRequest::new();
-        req.set_session_id(request.session_id);
-        req.set_initial_cycle(request.time);
-
-        marshaller.write(&req).unwrap()
+    fn from(request: SessionStepRequest) -> Self {
+        ProtobufCodec.encode_step(request).unwrap()
     }
 }
 
 impl From<NewSessionRequest>
     for Vec<u8>
 {
-    fn from(
-        request: NewSessionRequest,
-    ) -> Self {
-        let marshaller: Box<dyn Marshaller<manager_high::NewSessionRequest> + Sync + Send> = Box::new(grpc::protobuf::MarshallerProtobuf);
-    
-        let mut req = manager_high::NewSessionRequest::new();
-        req.set_session_id(request.session_id);
-        req.set_machine(request.machine);
-
-        marshaller.write(&req).unwrap()
+    fn from(request: NewSessionRequest) -> Self {
+        ProtobufCodec.encode_new_session(request).unwrap()
     }
 }
 
 impl From<SessionReadMemoryRequest>
     for Vec<u8>
 {
-    fn from(
-        request: SessionReadMemoryRequest,
-    ) -> Self {
-        let marshaller: Box<dyn Marshaller<manager_high::SessionReadMemoryRequest> + Sync + Send> = Box::new(grpc::protobuf::MarshallerProtobuf);
-    
-        let mut req = manager_high::SessionReadMemoryRequest::new();
-        req.set_session_id(request.session_id);
-        req.set_cycle(request.time);
-        req.set_position(request.position);
-
-        marshaller.write(&req).unwrap()
+    fn from(request: SessionReadMemoryRequest) -> Self {
+        ProtobufCodec.encode_read_memory(request).unwrap()
     }
 }
 
 impl From<SessionGetProofRequest>
     for Vec<u8>
 {
-    fn from(
-        request: SessionGetProofRequest,
-    ) -> Self {
-        let marshaller: Box<dyn Marshaller<manager_high::SessionGetProofRequest> + Sync + Send> = Box::new(grpc::protobuf::MarshallerProtobuf);
-    
-        let mut req = manager_high::SessionGetProofRequest::new();
-        req.set_session_id(request.session_id);
-        req.set_cycle(request.time);
-        req.set_target(request.target);
+    fn from(request: SessionGetProofRequest) -> Self {
+        ProtobufCodec.encode_get_proof(request).unwrap()
+    }
+}
+
+/// Generated readers/builders for `schemas/session.capnp`, emitted by
+/// `build.rs` into `OUT_DIR` when the `capnp` feature is enabled.
+#[cfg(feature = "capnp")]
+pub mod session_capnp {
+    include!(concat!(env!("OUT_DIR"), "/session_capnp.rs"));
+}
+
+// `write_to_vec` on the embedded protobuf payloads is a `protobuf::Message`
+// trait method; bring the trait into scope for the Cap'n Proto backend.
+#[cfg(feature = "capnp")]
+use protobuf::Message;
+
+/// A [`SessionCodec`] that frames messages with Cap'n Proto instead of
+/// protobuf. It reuses the typed wrapper structs unchanged; only the wire
+/// representation differs. Enabled behind the `capnp` feature so sessions can
+/// opt into zero-copy framing for the large access logs and memory blobs.
+#[cfg(feature = "capnp")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CapnpCodec;
+
+#[cfg(feature = "capnp")]
+impl CapnpCodec {
+    fn read_hash(reader: session_capnp::hash::Reader) -> Result<H256, ManagerError> {
+        h256_from(
+            "hash",
+            reader.get_content().map_err(|_| ManagerError::BadMarshalling)?,
+        )
+    }
+
+    fn read_proof(reader: session_capnp::proof::Reader) -> Result<Proof, ManagerError> {
+        let siblings = reader
+            .get_sibling_hashes()
+            .map_err(|_| ManagerError::BadMarshalling)?;
+        let mut sibling_hashes = Vec::with_capacity(siblings.len() as usize);
+        for sibling in siblings.iter() {
+            sibling_hashes.push(Self::read_hash(sibling)?);
+        }
+        Ok(Proof {
+            address: reader.get_address(),
+            log2_size: reader.get_log2_size(),
+            target_hash: Self::read_hash(
+                reader.get_target_hash().map_err(|_| ManagerError::BadMarshalling)?,
+            )?,
+            sibling_hashes,
+            root_hash: Self::read_hash(
+                reader.get_root_hash().map_err(|_| ManagerError::BadMarshalling)?,
+            )?,
+        })
+    }
+}
+
+#[cfg(feature = "capnp")]
+impl SessionCodec for CapnpCodec {
+    fn encode_new_session(&self, request: NewSessionRequest) -> Result<Vec<u8>, ManagerError> {
+        let mut message = capnp::message::Builder::new_default();
+        {
+            let mut root = message.init_root::<session_capnp::new_session_request::Builder>();
+            root.set_session_id(&request.session_id);
+            let mut machine = Vec::new();
+            request
+                .machine
+                .write_to_vec(&mut machine)
+                .map_err(|_| ManagerError::BadMarshalling)?;
+            root.set_machine(&machine);
+        }
+        let mut out = Vec::new();
+        capnp::serialize::write_message(&mut out, &message)
+            .map_err(|_| ManagerError::BadMarshalling)?;
+        Ok(out)
+    }
+
+    fn encode_run(&self, request: SessionRunRequest) -> Result<Vec<u8>, ManagerError> {
+        let mut message = capnp::message::Builder::new_default();
+        {
+            let mut root = message.init_root::<session_capnp::session_run_request::Builder>();
+            root.set_session_id(&request.session_id);
+            let mut cycles = root.reborrow().init_final_cycles(request.times.len() as u32);
+            for (i, cycle) in request.times.iter().enumerate() {
+                cycles.set(i as u32, *cycle);
+            }
+        }
+        let mut out = Vec::new();
+        capnp::serialize::write_message(&mut out, &message)
+            .map_err(|_| ManagerError::BadMarshalling)?;
+        Ok(out)
+    }
+
+    fn encode_step(&self, request: SessionStepRequest) -> Result<Vec<u8>, ManagerError> {
+        let mut message = capnp::message::Builder::new_default();
+        {
+            let mut root = message.init_root::<session_capnp::session_step_request::Builder>();
+            root.set_session_id(&request.session_id);
+            root.set_initial_cycle(request.time);
+        }
+        let mut out = Vec::new();
+        capnp::serialize::write_message(&mut out, &message)
+            .map_err(|_| ManagerError::BadMarshalling)?;
+        Ok(out)
+    }
+
+    fn encode_read_memory(&self, request: SessionReadMemoryRequest) -> Result<Vec<u8>, ManagerError> {
+        let mut message = capnp::message::Builder::new_default();
+        {
+            let mut root = message.init_root::<session_capnp::session_read_memory_request::Builder>();
+            root.set_session_id(&request.session_id);
+            root.set_cycle(request.time);
+            let mut position = Vec::new();
+            request
+                .position
+                .write_to_vec(&mut position)
+                .map_err(|_| ManagerError::BadMarshalling)?;
+            root.set_position(&position);
+        }
+        let mut out = Vec::new();
+        capnp::serialize::write_message(&mut out, &message)
+            .map_err(|_| ManagerError::BadMarshalling)?;
+        Ok(out)
+    }
+
+    fn encode_get_proof(&self, request: SessionGetProofRequest) -> Result<Vec<u8>, ManagerError> {
+        let mut message = capnp::message::Builder::new_default();
+        {
+            let mut root = message.init_root::<session_capnp::session_get_proof_request::Builder>();
+            root.set_session_id(&request.session_id);
+            root.set_cycle(request.time);
+            let mut target = Vec::new();
+            request
+                .target
+                .write_to_vec(&mut target)
+                .map_err(|_| ManagerError::BadMarshalling)?;
+            root.set_target(&target);
+        }
+        let mut out = Vec::new();
+        capnp::serialize::write_message(&mut out, &message)
+            .map_err(|_| ManagerError::BadMarshalling)?;
+        Ok(out)
+    }
+
+    fn decode_new_session_request(&self, response: Vec<u8>) -> Result<NewSessionRequest, ManagerError> {
+        let reader = capnp::serialize::read_message(
+            &mut response.as_slice(),
+            capnp::message::ReaderOptions::new(),
+        )
+        .map_err(|_| ManagerError::BadMarshalling)?;
+        let root = reader
+            .get_root::<session_capnp::new_session_request::Reader>()
+            .map_err(|_| ManagerError::BadMarshalling)?;
+        let machine = protobuf::parse_from_bytes(
+            root.get_machine().map_err(|_| ManagerError::BadMarshalling)?,
+        )
+        .map_err(|_| ManagerError::BadMarshalling)?;
+        Ok(NewSessionRequest {
+            session_id: root
+                .get_session_id()
+                .map_err(|_| ManagerError::BadMarshalling)?
+                .to_string(),
+            machine,
+        })
+    }
+
+    fn decode_new_session_result(&self, response: Vec<u8>) -> Result<NewSessionResult, ManagerError> {
+        let reader = capnp::serialize::read_message(
+            &mut response.as_slice(),
+            capnp::message::ReaderOptions::new(),
+        )
+        .map_err(|_| ManagerError::BadMarshalling)?;
+        let root = reader
+            .get_root::<session_capnp::new_session_result::Reader>()
+            .map_err(|_| ManagerError::BadMarshalling)?;
+        Ok(NewSessionResult {
+            hash: Self::read_hash(
+                root.get_hash().map_err(|_| ManagerError::BadMarshalling)?,
+            )?,
+        })
+    }
+
+    fn decode_run_result(&self, response: Vec<u8>) -> Result<SessionRunResult, ManagerError> {
+        let reader = capnp::serialize::read_message(
+            &mut response.as_slice(),
+            capnp::message::ReaderOptions::new(),
+        )
+        .map_err(|_| ManagerError::BadMarshalling)?;
+        let root = reader
+            .get_root::<session_capnp::session_run_result::Reader>()
+            .map_err(|_| ManagerError::BadMarshalling)?;
+        let hashes = root.get_hashes().map_err(|_| ManagerError::BadMarshalling)?;
+        let mut out = Vec::with_capacity(hashes.len() as usize);
+        for hash in hashes.iter() {
+            out.push(Self::read_hash(hash)?);
+        }
+        Ok(SessionRunResult { hashes: out })
+    }
+
+    fn decode_step_result(&self, response: Vec<u8>) -> Result<SessionStepResult, ManagerError> {
+        let reader = capnp::serialize::read_message(
+            &mut response.as_slice(),
+            capnp::message::ReaderOptions::new(),
+        )
+        .map_err(|_| ManagerError::BadMarshalling)?;
+        let root = reader
+            .get_root::<session_capnp::session_step_result::Reader>()
+            .map_err(|_| ManagerError::BadMarshalling)?;
+        let accesses = root.get_log().map_err(|_| ManagerError::BadMarshalling)?;
+        let mut log = Vec::with_capacity(accesses.len() as usize);
+        for access in accesses.iter() {
+            let operation = match access
+                .get_operation()
+                .map_err(|_| ManagerError::BadMarshalling)?
+            {
+                session_capnp::AccessOperation::Read => AccessOperation::Read,
+                session_capnp::AccessOperation::Write => AccessOperation::Write,
+            };
+            let proof = Self::read_proof(
+                access.get_proof().map_err(|_| ManagerError::BadMarshalling)?,
+            )?;
+            log.push(Access {
+                operation,
+                address: access.get_address(),
+                value_read: to_bytes(
+                    "value_read",
+                    access
+                        .get_value_read()
+                        .map_err(|_| ManagerError::BadMarshalling)?
+                        .to_vec(),
+                )?,
+                value_written: to_bytes(
+                    "value_written",
+                    access
+                        .get_value_written()
+                        .map_err(|_| ManagerError::BadMarshalling)?
+                        .to_vec(),
+                )?,
+                proof,
+            });
+        }
+        Ok(SessionStepResult { log })
+    }
+
+    fn decode_read_memory_result(&self, response: Vec<u8>) -> Result<SessionReadMemoryResult, ManagerError> {
+        let reader = capnp::serialize::read_message(
+            &mut response.as_slice(),
+            capnp::message::ReaderOptions::new(),
+        )
+        .map_err(|_| ManagerError::BadMarshalling)?;
+        let root = reader
+            .get_root::<session_capnp::session_read_memory_result::Reader>()
+            .map_err(|_| ManagerError::BadMarshalling)?;
+        let content = root
+            .get_read_content()
+            .map_err(|_| ManagerError::BadMarshalling)?;
+        Ok(SessionReadMemoryResult {
+            read_content: ReadMemoryResponse {
+                data: content
+                    .get_data()
+                    .map_err(|_| ManagerError::BadMarshalling)?
+                    .to_vec(),
+            },
+        })
+    }
+
+    fn decode_get_proof_result(&self, response: Vec<u8>) -> Result<SessionGetProofResult, ManagerError> {
+        let reader = capnp::serialize::read_message(
+            &mut response.as_slice(),
+            capnp::message::ReaderOptions::new(),
+        )
+        .map_err(|_| ManagerError::BadMarshalling)?;
+        let root = reader
+            .get_root::<session_capnp::session_get_proof_result::Reader>()
+            .map_err(|_| ManagerError::BadMarshalling)?;
+        Ok(SessionGetProofResult {
+            proof: Self::read_proof(
+                root.get_proof().map_err(|_| ManagerError::BadMarshalling)?,
+            )?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod proof_tests {
+    use super::*;
+
+    /// Build a proof whose `root_hash` is the value recomputed from `leaf`
+    /// and `siblings`, so it verifies by construction.
+    fn consistent(address: u64, log2_size: u32, leaf: H256, siblings: Vec<H256>) -> Proof {
+        let mut proof = Proof {
+            address,
+            log2_size,
+            target_hash: leaf,
+            sibling_hashes: siblings,
+            root_hash: H256::zero(),
+        };
+        proof.root_hash = proof.recompute_root(leaf);
+        proof
+    }
+
+    #[test]
+    fn accepts_consistent_proof() {
+        let siblings = vec![
+            H256::repeat_byte(0x22),
+            H256::repeat_byte(0x33),
+            H256::repeat_byte(0x44),
+        ];
+        let proof = consistent(0b101 << 61, 61, H256::repeat_byte(0x11), siblings);
+        assert_eq!(proof.verify(), Ok(()));
+        assert!(proof.is_valid());
+    }
+
+    #[test]
+    fn rejects_tampered_root() {
+        let siblings = vec![H256::repeat_byte(0x22); 3];
+        let mut proof = consistent(0, 61, H256::repeat_byte(0x11), siblings);
+        proof.root_hash = H256::repeat_byte(0xff);
+        match proof.verify() {
+            Err(ProofError::RootMismatch { .. }) => {}
+            other => panic!("expected RootMismatch, got {:?}", other),
+        }
+        assert!(!proof.is_valid());
+    }
+
+    #[test]
+    fn rejects_wrong_sibling_count() {
+        let mut proof = consistent(0, 61, H256::repeat_byte(0x11), vec![H256::repeat_byte(0x22); 3]);
+        proof.sibling_hashes.push(H256::repeat_byte(0x55));
+        assert_eq!(
+            proof.verify(),
+            Err(ProofError::WrongSiblingCount { expected: 3, found: 4 })
+        );
+    }
+
+    #[test]
+    fn rejects_subword_size() {
+        let proof = Proof {
+            address: 0,
+            log2_size: 2,
+            target_hash: H256::zero(),
+            sibling_hashes: vec![],
+            root_hash: H256::zero(),
+        };
+        assert_eq!(proof.verify(), Err(ProofError::TooSmall));
+    }
+
+    #[test]
+    fn rejects_oversized_size() {
+        let proof = Proof {
+            address: 0,
+            log2_size: MACHINE_TREE_LOG2_SIZE + 1,
+            target_hash: H256::zero(),
+            sibling_hashes: vec![],
+            root_hash: H256::zero(),
+        };
+        assert_eq!(proof.verify(), Err(ProofError::TooLarge));
+    }
+}
+
+#[cfg(test)]
+mod step_tests {
+    use super::*;
+
+    const LOG2_SIZE: u32 = 61;
+
+    fn siblings() -> Vec<H256> {
+        vec![
+            H256::repeat_byte(0x22),
+            H256::repeat_byte(0x33),
+            H256::repeat_byte(0x44),
+        ]
+    }
+
+    fn proof_for(leaf: H256, siblings: Vec<H256>) -> Proof {
+        let mut proof = Proof {
+            address: 0,
+            log2_size: LOG2_SIZE,
+            target_hash: leaf,
+            sibling_hashes: siblings,
+            root_hash: H256::zero(),
+        };
+        proof.root_hash = proof.recompute_root(leaf);
+        proof
+    }
+
+    fn read(word: [u8; 8]) -> Access {
+        Access {
+            operation: AccessOperation::Read,
+            address: 0,
+            value_read: word,
+            value_written: [0; 8],
+            proof: proof_for(hash_word(&word), siblings()),
+        }
+    }
+
+    fn write(old: [u8; 8], new: [u8; 8]) -> Access {
+        Access {
+            operation: AccessOperation::Write,
+            address: 0,
+            value_read: old,
+            value_written: new,
+            proof: proof_for(hash_word(&old), siblings()),
+        }
+    }
+
+    /// Root of the state reached after the write in `write(old, new)`.
+    fn post_of(new: [u8; 8]) -> H256 {
+        proof_for(hash_word(&new), siblings()).recompute_root(hash_word(&new))
+    }
+
+    #[test]
+    fn accepts_consistent_step() {
+        let w = [1, 0, 0, 0, 0, 0, 0, 0];
+        let written = [2, 0, 0, 0, 0, 0, 0, 0];
+        let result = SessionStepResult {
+            log: vec![read(w), write(w, written)],
+        };
+        let pre = result.log[0].proof.root_hash;
+        let post = post_of(written);
+        assert_eq!(result.verify(pre, post), Ok(()));
+    }
+
+    #[test]
+    fn rejects_empty_log() {
+        let result = SessionStepResult { log: vec![] };
+        assert_eq!(result.verify(H256::zero(), H256::zero()), Err(StepError::EmptyLog));
+    }
+
+    #[test]
+    fn rejects_inconsistent_read_value() {
+        let mut access = read([1, 0, 0, 0, 0, 0, 0, 0]);
+        access.value_read = [9, 0, 0, 0, 0, 0, 0, 0];
+        let pre = access.proof.root_hash;
+        let result = SessionStepResult { log: vec![access] };
+        assert_eq!(result.verify(pre, pre), Err(StepError::ReadMismatch { index: 0 }));
+    }
+
+    #[test]
+    fn rejects_broken_chain() {
+        let access = read([1, 0, 0, 0, 0, 0, 0, 0]);
+        let root = access.proof.root_hash;
+        let result = SessionStepResult { log: vec![access] };
+        match result.verify(H256::repeat_byte(0xab), root) {
+            Err(StepError::RootMismatch { index: 0, .. }) => {}
+            other => panic!("expected RootMismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_wrong_post_state() {
+        let access = read([1, 0, 0, 0, 0, 0, 0, 0]);
+        let pre = access.proof.root_hash;
+        let result = SessionStepResult { log: vec![access] };
+        match result.verify(pre, H256::repeat_byte(0xcd)) {
+            Err(StepError::PostMismatch { .. }) => {}
+            other => panic!("expected PostMismatch, got {:?}", other),
+        }
+    }
+}
+
+#[cfg(test)]
+mod codec_tests {
+    use super::*;
+
+    fn sample_request() -> NewSessionRequest {
+        NewSessionRequest {
+            machine: cartesi_base::MachineRequest::new(),
+            session_id: "session-42".to_string(),
+        }
+    }
+
+    #[test]
+    fn protobuf_new_session_round_trip() {
+        let codec = ProtobufCodec;
+        let bytes = codec.encode_new_session(sample_request()).unwrap();
+        let decoded = codec.decode_new_session_request(bytes).unwrap();
+        assert_eq!(decoded.session_id, "session-42");
+    }
 
-        marshaller.write(&req).unwrap()
+    #[cfg(feature = "capnp")]
+    #[test]
+    fn capnp_new_session_round_trip() {
+        let codec = CapnpCodec;
+        let bytes = codec.encode_new_session(sample_request()).unwrap();
+        let decoded = codec.decode_new_session_request(bytes).unwrap();
+        assert_eq!(decoded.session_id, "session-42");
     }
 }